@@ -1,12 +1,17 @@
-use aho_corasick::{AhoCorasickBuilder, MatchKind};
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
 use anyhow::{Context, Error};
 use async_std::{fs::File, io::BufReader, prelude::*};
 use colored::*;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
 use std::{
-    collections::{BTreeSet, HashMap, HashSet},
-    hash::Hash,
-    path::PathBuf,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc::channel,
+    time::Duration,
 };
 use structopt::StructOpt;
 use walkdir::{DirEntry, WalkDir};
@@ -14,100 +19,870 @@ use walkdir::{DirEntry, WalkDir};
 #[derive(StructOpt)]
 struct Args {
     dir: PathBuf,
+
+    /// Keep running after the initial pass and re-lint notes as they change on disk
+    #[structopt(long)]
+    watch: bool,
+
+    /// Path to an obslint.toml config file (defaults to <dir>/obslint.toml if present)
+    #[structopt(long)]
+    config: Option<PathBuf>,
+
+    /// Match note names case-insensitively, reporting mentions under the note's real casing
+    #[structopt(long)]
+    ignore_case: bool,
+
+    /// Rewrite unlinked mentions in place, wrapping each in [[wikilinks]]
+    #[structopt(long)]
+    fix: bool,
+
+    /// With --fix, print a unified diff of the proposed changes instead of writing them
+    #[structopt(long)]
+    dry_run: bool,
+
+    /// Match inside fenced/indented code blocks, inline code, and link targets too (by default
+    /// these are skipped)
+    #[structopt(long)]
+    no_code_blocks: bool,
+
+    /// Output format for unlinked mentions: human, json, or ndjson
+    #[structopt(long, default_value = "human")]
+    format: Format,
+}
+
+impl Args {
+    fn code_blocks_enabled(&self) -> bool {
+        !self.no_code_blocks
+    }
+}
+
+/// Vault-specific tuning read from `obslint.toml`. CLI flags win over whatever is set here.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Config {
+    ignore: Vec<String>,
+    ignore_case: Option<bool>,
+    min_mention_len: Option<usize>,
+    stopwords: Vec<String>,
+}
+
+fn load_config(args: &Args) -> Result<Config, Error> {
+    let path = args
+        .config
+        .clone()
+        .unwrap_or_else(|| args.dir.join("obslint.toml"));
+
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let raw = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read config file {}", path.display()))?;
+    toml::from_str(&raw).with_context(|| format!("failed to parse config file {}", path.display()))
+}
+
+/// `Args` merged with the vault's `obslint.toml`, with CLI flags taking priority.
+struct Settings {
+    ignore_case: bool,
+    ignore: GlobSet,
+    min_mention_len: usize,
+    stopwords: HashSet<String>,
+}
+
+impl Settings {
+    fn merge(args: &Args, config: Config) -> Result<Self, Error> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &config.ignore {
+            for expanded in expand_ignore_pattern(pattern) {
+                builder.add(
+                    Glob::new(&expanded)
+                        .with_context(|| format!("invalid ignore pattern '{}'", pattern))?,
+                );
+            }
+        }
+
+        Ok(Settings {
+            ignore_case: args.ignore_case || config.ignore_case.unwrap_or(false),
+            ignore: builder.build()?,
+            min_mention_len: config.min_mention_len.unwrap_or(0),
+            stopwords: config.stopwords.iter().map(|w| w.to_lowercase()).collect(),
+        })
+    }
+}
+
+/// A bare `dir/` pattern only matches the literal string `"dir/"`, never a file inside it, which
+/// trips up the common case of wanting to ignore a whole directory. Expand it into the directory
+/// itself plus `dir/**` so `ignore = ["templates/"]` behaves the way users expect.
+fn expand_ignore_pattern(pattern: &str) -> Vec<String> {
+    match pattern.strip_suffix('/') {
+        Some(dir) if !dir.is_empty() => vec![dir.to_string(), format!("{}/**", dir)],
+        _ => vec![pattern.to_string()],
+    }
+}
+
+fn is_ignored(dir: &Path, path: &Path, ignore: &GlobSet) -> bool {
+    match path.strip_prefix(dir) {
+        Ok(relative) if !relative.as_os_str().is_empty() => ignore.is_match(relative),
+        _ => false,
+    }
+}
+
+fn filter_links(terms: BTreeMap<String, String>, settings: &Settings) -> BTreeMap<String, String> {
+    terms
+        .into_iter()
+        .filter(|(term, _)| {
+            term.chars().count() >= settings.min_mention_len
+                && !settings.stopwords.contains(&term.to_lowercase())
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Human,
+    Json,
+    Ndjson,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Format::Human),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            other => Err(format!(
+                "unknown format '{}', expected human, json, or ndjson",
+                other
+            )),
+        }
+    }
 }
 
-#[derive(Debug, Eq)]
+/// A single accepted, still-unlinked mention of another note, located within its file.
+#[derive(Serialize)]
+struct Mention {
+    term: String,
+    line: usize,
+    column: usize,
+    byte_start: usize,
+    byte_end: usize,
+}
+
+/// All unlinked mentions found in one note, keyed by its path relative to `args.dir`.
+#[derive(Serialize)]
+struct NoteReport {
+    path: String,
+    mentions: Vec<Mention>,
+}
+
+fn line_column(content: &str, byte_offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut last_newline = None;
+
+    for (i, b) in content.as_bytes()[..byte_offset].iter().enumerate() {
+        if *b == b'\n' {
+            line += 1;
+            last_newline = Some(i);
+        }
+    }
+
+    let line_start = last_newline.map_or(0, |i| i + 1);
+    let column = content[line_start..byte_offset].chars().count() + 1;
+
+    (line, column)
+}
+
+#[derive(Debug)]
 struct Note {
     path: PathBuf,
     name: String,
     content: String,
 }
 
-impl Hash for Note {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.path.hash(state)
-    }
+type Links = HashMap<PathBuf, BTreeSet<String>>;
+
+/// The searchable dictionary of matchable terms (existing wikilink targets, note titles, and
+/// frontmatter aliases), plus a table back to the canonical name each term resolves to so a
+/// hit on an alias is still reported under the note's real title.
+struct Index {
+    searcher: AhoCorasick,
+    canonical: HashMap<String, String>,
+    ignore_case: bool,
 }
 
-impl PartialEq for Note {
-    fn eq(&self, other: &Self) -> bool {
-        self.path.eq(&other.path)
+impl Index {
+    /// `terms` maps each matchable term to the canonical name it should be reported as.
+    fn build(terms: &BTreeMap<String, String>, ignore_case: bool) -> Self {
+        let canonical = terms
+            .iter()
+            .map(|(term, canonical)| {
+                let key = if ignore_case {
+                    term.to_lowercase()
+                } else {
+                    term.clone()
+                };
+                (key, canonical.clone())
+            })
+            .collect();
+
+        let searcher = AhoCorasickBuilder::new()
+            .match_kind(MatchKind::LeftmostLongest)
+            .ascii_case_insensitive(ignore_case)
+            .dfa(true)
+            .build(terms.keys());
+
+        Index {
+            searcher,
+            canonical,
+            ignore_case,
+        }
+    }
+
+    fn canonicalize(&self, s: &str) -> Option<&str> {
+        let key = if self.ignore_case {
+            s.to_lowercase()
+        } else {
+            s.to_string()
+        };
+        self.canonical.get(&key).map(|s| s.as_str())
     }
 }
 
 #[async_std::main]
 async fn main() -> Result<(), Error> {
     let args = Args::from_args();
-    let paths = WalkDir::new(&args.dir).into_iter().filter_map(|e| e.ok());
+    let config = load_config(&args)?;
+    let settings = Settings::merge(&args, config)?;
+
+    let mut notes = scan_notes(&args.dir, &settings.ignore).await?;
+    let mut links = build_links(&notes);
+    let mut terms = filter_links(build_terms(&all_links(&links), &notes), &settings);
+    let mut index = Index::build(&terms, settings.ignore_case);
+
+    let pending: Vec<Unlinked> = notes
+        .par_iter()
+        .filter_map(|(path, _)| find_unlinked(&args, &notes, &links, &index, path))
+        .collect();
+
+    let mut reports = Vec::new();
+    for nm in pending {
+        if let Some(note_report) = report(&args, nm).await? {
+            reports.push(note_report);
+        }
+    }
+    print_reports(args.format, &reports);
+
+    if args.watch {
+        watch(
+            &args, &settings, &mut notes, &mut links, &mut terms, &mut index,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn scan_notes(dir: &Path, ignore: &GlobSet) -> Result<HashMap<PathBuf, Note>, Error> {
+    let paths = WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|e| !is_ignored(dir, e.path(), ignore))
+        .filter_map(|e| e.ok());
 
     let mut fs = Vec::new();
     for path in paths {
         fs.push(process_file(path));
     }
 
-    let notes: Vec<Note> = futures::future::try_join_all(fs)
+    let notes = futures::future::try_join_all(fs)
         .await
         .context("failed while processing files")?
         .into_iter()
         .filter_map(|o| o)
+        .map(|note| (note.path.clone(), note))
         .collect();
 
-    let links: HashMap<&Note, BTreeSet<&str>> = notes
+    Ok(notes)
+}
+
+fn build_links(notes: &HashMap<PathBuf, Note>) -> Links {
+    notes
         .par_iter()
-        .map(|note| (note, wikilinks(&note.content)))
+        .map(|(path, note)| {
+            (
+                path.clone(),
+                wikilinks(&note.content)
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+fn all_links(links: &Links) -> BTreeSet<String> {
+    links
+        .values()
+        .flatten()
+        .filter(|l| !l.is_empty())
+        .cloned()
+        .collect()
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Frontmatter {
+    aliases: Vec<String>,
+}
+
+/// Parses the YAML frontmatter block (`---` ... `---`) at the top of a note, if any.
+fn parse_frontmatter(content: &str) -> Frontmatter {
+    let body = match content
+        .strip_prefix("---\r\n")
+        .or_else(|| content.strip_prefix("---\n"))
+    {
+        Some(body) => body,
+        None => return Frontmatter::default(),
+    };
+
+    let end = match body.find("\n---") {
+        Some(i) => i,
+        None => return Frontmatter::default(),
+    };
+
+    serde_yaml::from_str(&body[..end]).unwrap_or_default()
+}
+
+fn note_title(note: &Note) -> &str {
+    note.name.strip_suffix(".md").unwrap_or(&note.name)
+}
+
+/// The dictionary of matchable terms: existing wikilink targets (self-canonical, as before)
+/// plus each note's own title and frontmatter aliases, resolving to that note's title. A
+/// term that's both an existing link target and a note's title/alias keeps the former, since
+/// it's already the spelling the vault uses.
+fn build_terms(
+    all_links: &BTreeSet<String>,
+    notes: &HashMap<PathBuf, Note>,
+) -> BTreeMap<String, String> {
+    let mut terms: BTreeMap<String, String> =
+        all_links.iter().map(|l| (l.clone(), l.clone())).collect();
+
+    // `notes` is a HashMap, so its iteration order is randomized per process; sort by path
+    // first so that when two notes share a title/alias, which one wins is deterministic
+    // (and reproducible across runs) rather than depending on hash iteration order.
+    let mut sorted_notes: Vec<&Note> = notes.values().collect();
+    sorted_notes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    for note in sorted_notes {
+        let title = note_title(note);
+        if title.is_empty() {
+            continue;
+        }
+
+        terms
+            .entry(title.to_string())
+            .or_insert_with(|| title.to_string());
+
+        for alias in parse_frontmatter(&note.content).aliases {
+            if !alias.is_empty() {
+                terms.entry(alias).or_insert_with(|| title.to_string());
+            }
+        }
+    }
+
+    terms
+}
+
+/// Matches the searcher accepts after the alphanumeric-boundary check, resolved to their
+/// canonical (real-cased) spelling and kept alongside their byte span so callers can either
+/// report them or splice wikilinks around them. Matches falling inside code blocks, inline
+/// code, or a Markdown link target are dropped when `code_blocks` is true.
+fn accepted_matches<'i>(
+    content: &str,
+    index: &'i Index,
+    code_blocks: bool,
+) -> Vec<(usize, usize, &'i str)> {
+    let excluded = if code_blocks {
+        excluded_ranges(content)
+    } else {
+        Vec::new()
+    };
+
+    let mut matches = Vec::new();
+    for mat in index.searcher.find_iter(content) {
+        if let Some(c) = char_prior_to(mat.start(), content) {
+            if c.is_alphanumeric() {
+                continue;
+            }
+        }
+
+        if let Some(c) = content[mat.end()..].chars().next() {
+            if c.is_alphanumeric() {
+                continue;
+            }
+        }
+
+        if overlaps_any(mat.start(), mat.end(), &excluded) {
+            continue;
+        }
+
+        if let Some(canonical) = index.canonicalize(&content[mat.start()..mat.end()]) {
+            matches.push((mat.start(), mat.end(), canonical));
+        }
+    }
+    matches
+}
+
+fn overlaps_any(start: usize, end: usize, ranges: &[(usize, usize)]) -> bool {
+    ranges.iter().any(|(rs, re)| start < *re && *rs < end)
+}
+
+/// Byte ranges that shouldn't be matched against: fenced and indented code blocks, inline
+/// `code` spans, and the `(...)` target of a Markdown `[text](target)` link.
+fn excluded_ranges(s: &str) -> Vec<(usize, usize)> {
+    let mut ranges = fenced_code_ranges(s);
+    ranges.extend(indented_code_ranges(s));
+    ranges.extend(inline_code_ranges(s));
+    ranges.extend(link_target_ranges(s));
+    ranges
+}
+
+fn lines_with_offsets(s: &str) -> Vec<(usize, usize, &str)> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if c == '\n' {
+            lines.push((start, i, &s[start..i]));
+            start = i + 1;
+        }
+    }
+    lines.push((start, s.len(), &s[start..]));
+    lines
+}
+
+fn fenced_code_ranges(s: &str) -> Vec<(usize, usize)> {
+    let lines = lines_with_offsets(s);
+    let mut ranges = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let (start, end, line) = lines[i];
+        let trimmed = line.trim_start();
+        let fence_char = trimmed.chars().next().filter(|&c| c == '`' || c == '~');
+        let fence_len = trimmed
+            .chars()
+            .take_while(|&c| Some(c) == fence_char)
+            .count();
+
+        if fence_char.is_none() || fence_len < 3 {
+            i += 1;
+            continue;
+        }
+
+        let mut close_end = end;
+        let mut j = i + 1;
+        while j < lines.len() {
+            let (_, e, line) = lines[j];
+            close_end = e;
+            j += 1;
+
+            let trimmed = line.trim_start();
+            let len = trimmed
+                .chars()
+                .take_while(|&c| Some(c) == fence_char)
+                .count();
+            if trimmed.chars().next() == fence_char && len >= fence_len {
+                break;
+            }
+        }
+
+        ranges.push((start, close_end));
+        i = j;
+    }
+
+    ranges
+}
+
+fn indented_code_ranges(s: &str) -> Vec<(usize, usize)> {
+    let lines = lines_with_offsets(s);
+    let mut ranges = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let (start, end, line) = lines[i];
+        if line.trim().is_empty() || !(line.starts_with("    ") || line.starts_with('\t')) {
+            i += 1;
+            continue;
+        }
+
+        let mut block_end = end;
+        let mut j = i + 1;
+        while j < lines.len() {
+            let (_, e, line) = lines[j];
+            if line.trim().is_empty() || line.starts_with("    ") || line.starts_with('\t') {
+                block_end = e;
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        ranges.push((start, block_end));
+        i = j;
+    }
+
+    ranges
+}
+
+fn inline_code_ranges(s: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut open = None;
+
+    for (i, c) in s.char_indices() {
+        if c != '`' {
+            continue;
+        }
+
+        match open {
+            None => open = Some(i),
+            Some(start) => {
+                ranges.push((start, i + c.len_utf8()));
+                open = None;
+            }
+        }
+    }
+
+    ranges
+}
+
+fn link_target_ranges(s: &str) -> Vec<(usize, usize)> {
+    let bytes = s.as_bytes();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+
+    while i + 1 < bytes.len() {
+        if bytes[i] != b']' || bytes[i + 1] != b'(' {
+            i += 1;
+            continue;
+        }
+
+        let start = i + 1;
+        let mut depth = 0;
+        let mut j = start;
+        while j < bytes.len() {
+            match bytes[j] {
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        ranges.push((start, j + 1));
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            j += 1;
+        }
+
+        i = j + 1;
+    }
+
+    ranges
+}
+
+/// A note's accepted, still-unlinked mentions, computed by the CPU-bound Aho-Corasick matching
+/// that `find_unlinked` runs in parallel across the vault. Kept separate from `report`'s async
+/// `--fix`/printing step so that step is the only part that has to run sequentially.
+struct Unlinked<'a> {
+    note: &'a Note,
+    matches: Vec<(usize, usize, &'a str)>,
+    unlinked: BTreeSet<&'a str>,
+}
+
+/// Looks up `path`'s note and returns its accepted matches that aren't already wikilinked
+/// (dropping any match that resolves to the note's own title, which covers its title and its
+/// aliases, since a note can't meaningfully link to itself; an alias that is also an existing
+/// wikilink target resolves to itself and is not dropped). Pure and CPU-bound, so callers can run it across notes with rayon.
+fn find_unlinked<'a>(
+    args: &Args,
+    notes: &'a HashMap<PathBuf, Note>,
+    links: &Links,
+    index: &'a Index,
+    path: &Path,
+) -> Option<Unlinked<'a>> {
+    let note = notes.get(path)?;
+    let note_links = links.get(path).unwrap();
+    let canonical_links: BTreeSet<&str> = note_links
+        .iter()
+        .filter_map(|l| index.canonicalize(l))
+        .collect();
+    let own_title = note_title(note);
+
+    let matches: Vec<_> = accepted_matches(&note.content, index, args.code_blocks_enabled())
+        .into_iter()
+        .filter(|(_, _, canonical)| *canonical != own_title)
         .collect();
+    let found: BTreeSet<&str> = matches.iter().map(|(_, _, canonical)| *canonical).collect();
+    let unlinked: BTreeSet<&str> = found.difference(&canonical_links).map(|s| *s).collect();
 
-    let all_links: BTreeSet<&str> = links
+    if unlinked.is_empty() {
+        None
+    } else {
+        Some(Unlinked {
+            note,
+            matches,
+            unlinked,
+        })
+    }
+}
+
+/// Applies `--fix` if requested and returns `None`. Otherwise, for `human` output prints
+/// `nm`'s unlinked mentions directly and returns `None`; for `json`/`ndjson` returns them as a
+/// `NoteReport` so the caller can collect reports across notes and print them together.
+async fn report(args: &Args, nm: Unlinked<'_>) -> Result<Option<NoteReport>, Error> {
+    let Unlinked {
+        note,
+        matches,
+        unlinked,
+    } = nm;
+
+    if args.fix {
+        apply_fix(args, note, &matches, &unlinked).await?;
+        return Ok(None);
+    }
+
+    let rel_path = note
+        .path
+        .strip_prefix(&args.dir)
+        .unwrap()
+        .to_string_lossy()
+        .to_string();
+
+    if args.format == Format::Human {
+        println!(
+            "{}: {}",
+            rel_path.blue(),
+            unlinked.into_iter().collect::<Vec<_>>().join(", ")
+        );
+        return Ok(None);
+    }
+
+    let mentions = matches
         .iter()
-        .flat_map(|(_, l)| l.iter().map(|s| *s))
-        .filter(|l| !l.is_empty())
+        .filter(|(_, _, canonical)| unlinked.contains(canonical))
+        .map(|(start, end, canonical)| {
+            let (line, column) = line_column(&note.content, *start);
+            Mention {
+                term: canonical.to_string(),
+                line,
+                column,
+                byte_start: *start,
+                byte_end: *end,
+            }
+        })
         .collect();
 
-    let searcher = AhoCorasickBuilder::new()
-        .match_kind(MatchKind::LeftmostLongest)
-        .dfa(true)
-        .build(all_links);
+    Ok(Some(NoteReport {
+        path: rel_path,
+        mentions,
+    }))
+}
 
-    notes.par_iter().for_each(|note| {
-        let links = links.get(note).unwrap();
+fn print_reports(format: Format, reports: &[NoteReport]) {
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(reports).unwrap()),
+        Format::Ndjson => {
+            for report in reports {
+                println!("{}", serde_json::to_string(report).unwrap());
+            }
+        }
+        Format::Human => {}
+    }
+}
 
-        let mut found = BTreeSet::new();
-        for mat in searcher.find_iter(&note.content) {
-            if let Some(c) = char_prior_to(mat.start(), &note.content) {
-                if c.is_alphanumeric() {
-                    continue;
-                }
+/// Byte spans already wrapped in `[[wikilinks]]`, outer brackets included, so a match that
+/// falls inside one is skipped rather than turned into `[[[[x]]]]`.
+fn wikilink_spans(s: &str) -> Vec<(usize, usize)> {
+    let mut n_brackets = 0;
+    let mut in_wikilink = false;
+    let mut outer_start = 0;
+    let mut spans = Vec::new();
+
+    for (i, c) in s.char_indices() {
+        if c == '[' {
+            if n_brackets == 0 {
+                n_brackets += 1;
+                outer_start = i;
+                continue;
+            } else if n_brackets == 1 {
+                n_brackets += 1;
+                in_wikilink = true;
+                continue;
             }
+        }
 
-            if let Some(c) = &note.content[mat.end()..].chars().next() {
-                if c.is_alphanumeric() {
-                    continue;
+        if c == ']' {
+            if n_brackets == 2 {
+                n_brackets -= 1;
+                continue;
+            } else if n_brackets == 1 {
+                n_brackets -= 1;
+                if in_wikilink {
+                    in_wikilink = false;
+                    spans.push((outer_start, i + c.len_utf8()));
                 }
+                continue;
             }
+        }
+    }
+
+    spans
+}
+
+async fn apply_fix(
+    args: &Args,
+    note: &Note,
+    matches: &[(usize, usize, &str)],
+    unlinked: &BTreeSet<&str>,
+) -> Result<(), Error> {
+    let excluded = wikilink_spans(&note.content);
+
+    let mut edits: Vec<(usize, usize)> = matches
+        .iter()
+        .filter(|(start, end, canonical)| {
+            unlinked.contains(canonical)
+                && !excluded.iter().any(|(es, ee)| *start < *ee && *es < *end)
+        })
+        .map(|(start, end, _)| (*start, *end))
+        .collect();
+
+    if edits.is_empty() {
+        return Ok(());
+    }
+
+    edits.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let mut content = note.content.clone();
+    for (start, end) in &edits {
+        content.insert_str(*end, "]]");
+        content.insert_str(*start, "[[");
+    }
+
+    if args.dry_run {
+        print_diff(args, note, &content);
+    } else {
+        async_std::fs::write(&note.path, content).await?;
+    }
 
-            found.insert(&note.content[mat.start()..mat.end()]);
+    Ok(())
+}
+
+fn print_diff(args: &Args, note: &Note, new_content: &str) {
+    let path = note
+        .path
+        .strip_prefix(&args.dir)
+        .unwrap()
+        .to_string_lossy()
+        .to_string();
+
+    let diff = TextDiff::from_lines(note.content.as_str(), new_content);
+    print!(
+        "{}",
+        diff.unified_diff()
+            .header(&format!("a/{}", path), &format!("b/{}", path))
+    );
+}
+
+async fn watch(
+    args: &Args,
+    settings: &Settings,
+    notes: &mut HashMap<PathBuf, Note>,
+    links: &mut Links,
+    terms: &mut BTreeMap<String, String>,
+    index: &mut Index,
+) -> Result<(), Error> {
+    let (tx, rx) = channel();
+    let mut watcher = notify::watcher(tx, Duration::from_millis(200))?;
+    watcher.watch(&args.dir, RecursiveMode::Recursive)?;
+
+    loop {
+        let event = match rx.recv() {
+            Ok(event) => event,
+            Err(_) => break,
+        };
+
+        let touched = touched_paths(event);
+        if touched.is_empty() {
+            continue;
+        }
+
+        for path in &touched {
+            if !path.to_string_lossy().ends_with(".md")
+                || is_ignored(&args.dir, path, &settings.ignore)
+            {
+                notes.remove(path);
+                links.remove(path);
+                continue;
+            }
+
+            // A race between the filesystem event and this re-read is expected here (e.g. the
+            // file was deleted or renamed again before we got to it), so treat any read error
+            // the same as "the file is gone" instead of aborting the whole watch loop.
+            match process_file_path(path.clone()).await {
+                Ok(Some(note)) => {
+                    links.insert(
+                        note.path.clone(),
+                        wikilinks(&note.content)
+                            .into_iter()
+                            .map(String::from)
+                            .collect(),
+                    );
+                    notes.insert(note.path.clone(), note);
+                }
+                Ok(None) | Err(_) => {
+                    notes.remove(path);
+                    links.remove(path);
+                }
+            }
         }
 
-        let unlinked: Vec<&str> = found.difference(links).map(|s| *s).collect();
-        if !unlinked.is_empty() {
-            println!(
-                "{}: {}",
-                note.path
-                    .strip_prefix(&args.dir)
-                    .unwrap()
-                    .as_os_str()
-                    .to_string_lossy()
-                    .blue(),
-                unlinked.join(", ")
-            );
+        *terms = filter_links(build_terms(&self::all_links(links), notes), settings);
+        *index = Index::build(terms, settings.ignore_case);
+
+        let mut reports = Vec::new();
+        for path in &touched {
+            if let Some(nm) = find_unlinked(args, notes, links, index, path) {
+                if let Some(note_report) = report(args, nm).await? {
+                    reports.push(note_report);
+                }
+            }
         }
-    });
+        print_reports(args.format, &reports);
+    }
 
     Ok(())
 }
 
+fn touched_paths(event: DebouncedEvent) -> Vec<PathBuf> {
+    match event {
+        DebouncedEvent::Create(path)
+        | DebouncedEvent::Write(path)
+        | DebouncedEvent::Remove(path) => {
+            vec![path]
+        }
+        DebouncedEvent::Rename(from, to) => vec![from, to],
+        _ => Vec::new(),
+    }
+}
+
 async fn process_file(e: DirEntry) -> Result<Option<Note>, Error> {
     let path = e.into_path();
+    process_file_path(path).await
+}
+
+async fn process_file_path(path: PathBuf) -> Result<Option<Note>, Error> {
     if !path.to_string_lossy().ends_with(".md") {
         return Ok(None);
     }
@@ -252,4 +1027,261 @@ mod tests {
     fn test_char_prior_to(i: usize, s: &str) -> Option<char> {
         char_prior_to(i, s)
     }
+
+    #[test_case("Productivity", false => Some("Productivity"))]
+    #[test_case("productivity", false => None)]
+    #[test_case("PRODUCTIVITY", true => Some("Productivity"))]
+    #[test_case("productivity", true => Some("Productivity"))]
+    #[test_case("unknown", true => None)]
+    fn test_index_canonicalize(s: &str, ignore_case: bool) -> Option<&'static str> {
+        let terms: BTreeMap<String, String> =
+            vec![("Productivity".to_string(), "Productivity".to_string())]
+                .into_iter()
+                .collect();
+        let index = Index::build(&terms, ignore_case);
+        index.canonicalize(s).map(|s| match s {
+            "Productivity" => "Productivity",
+            other => panic!("unexpected canonical form {}", other),
+        })
+    }
+
+    #[test_case("---\naliases:\n  - Getting Started\n  - Onboarding\n---\nbody" => vec!["Getting Started".to_string(), "Onboarding".to_string()])]
+    #[test_case("---\r\naliases:\r\n  - Alias\r\n---\r\nbody" => vec!["Alias".to_string()] ; "crlf frontmatter")]
+    #[test_case("no frontmatter here" => Vec::<String>::new() ; "missing frontmatter")]
+    #[test_case("---\nunterminated" => Vec::<String>::new() ; "unterminated frontmatter")]
+    #[test_case("---\naliases: [not, a, map\n---\nbody" => Vec::<String>::new() ; "malformed yaml")]
+    fn test_parse_frontmatter(content: &str) -> Vec<String> {
+        parse_frontmatter(content).aliases
+    }
+
+    #[test_case("Productivity.md" => "Productivity")]
+    #[test_case("Notes" => "Notes" ; "no extension")]
+    fn test_note_title(name: &str) -> &str {
+        let note = Note {
+            path: PathBuf::from(name),
+            name: name.to_string(),
+            content: String::new(),
+        };
+        note_title(&note)
+    }
+
+    #[test]
+    fn test_build_terms_titles_and_aliases() {
+        let mut notes = HashMap::new();
+        notes.insert(
+            PathBuf::from("productivity.md"),
+            Note {
+                path: PathBuf::from("productivity.md"),
+                name: "productivity.md".to_string(),
+                content: "---\naliases:\n  - Getting Things Done\n---\nbody".to_string(),
+            },
+        );
+
+        let all_links: BTreeSet<String> = vec!["Index".to_string()].into_iter().collect();
+        let terms = build_terms(&all_links, &notes);
+
+        assert_eq!(terms.get("Index"), Some(&"Index".to_string()));
+        assert_eq!(terms.get("productivity"), Some(&"productivity".to_string()));
+        assert_eq!(
+            terms.get("Getting Things Done"),
+            Some(&"productivity".to_string())
+        );
+    }
+
+    #[test]
+    fn test_build_terms_existing_link_wins_over_title() {
+        let mut notes = HashMap::new();
+        notes.insert(
+            PathBuf::from("index.md"),
+            Note {
+                path: PathBuf::from("index.md"),
+                name: "index.md".to_string(),
+                content: String::new(),
+            },
+        );
+
+        let all_links: BTreeSet<String> = vec!["index".to_string()].into_iter().collect();
+        let terms = build_terms(&all_links, &notes);
+
+        assert_eq!(terms.get("index"), Some(&"index".to_string()));
+    }
+
+    #[test]
+    fn test_build_terms_title_collision_is_deterministic() {
+        let mut notes = HashMap::new();
+        notes.insert(
+            PathBuf::from("a.md"),
+            Note {
+                path: PathBuf::from("a.md"),
+                name: "a.md".to_string(),
+                content: String::new(),
+            },
+        );
+        notes.insert(
+            PathBuf::from("b.md"),
+            Note {
+                path: PathBuf::from("b.md"),
+                name: "b.md".to_string(),
+                content: "---\naliases:\n  - a\n---\nbody".to_string(),
+            },
+        );
+
+        let all_links = BTreeSet::new();
+        let terms = build_terms(&all_links, &notes);
+
+        // "a" is both a.md's own title and one of b.md's aliases. Notes are processed in
+        // path order, so a.md (processed first) should keep the term resolving to its own
+        // title, regardless of the HashMap's (randomized) iteration order.
+        assert_eq!(terms.get("a"), Some(&"a".to_string()));
+    }
+
+    #[test_case("hello world" => Vec::<(usize, usize)>::new())]
+    #[test_case("[[world]]" => vec![(0, 9)])]
+    #[test_case("[[hello]] world [[world]]" => vec![(0, 9), (16, 25)])]
+    #[test_case("[[hello|what]]" => vec![(0, 14)])]
+    fn test_wikilink_spans(s: &str) -> Vec<(usize, usize)> {
+        wikilink_spans(s)
+    }
+
+    #[test_case("hello world" => Vec::<(usize, usize)>::new())]
+    #[test_case("```\ncode\n```" => vec![(0, 12)])]
+    #[test_case("~~~\ncode\n~~~" => vec![(0, 12)])]
+    #[test_case("text\n```\ncode\n```\nmore" => vec![(5, 17)])]
+    #[test_case("```\nunterminated" => vec![(0, 16)])]
+    fn test_fenced_code_ranges(s: &str) -> Vec<(usize, usize)> {
+        fenced_code_ranges(s)
+    }
+
+    #[test_case("hello world" => Vec::<(usize, usize)>::new())]
+    #[test_case("    code line" => vec![(0, 13)])]
+    #[test_case("para\n    code\n    more\npara" => vec![(5, 22)])]
+    fn test_indented_code_ranges(s: &str) -> Vec<(usize, usize)> {
+        indented_code_ranges(s)
+    }
+
+    #[test_case("hello world" => Vec::<(usize, usize)>::new())]
+    #[test_case("`code`" => vec![(0, 6)])]
+    #[test_case("a `one` and `two`" => vec![(2, 7), (12, 17)])]
+    fn test_inline_code_ranges(s: &str) -> Vec<(usize, usize)> {
+        inline_code_ranges(s)
+    }
+
+    #[test_case("hello world" => Vec::<(usize, usize)>::new())]
+    #[test_case("[text](url)" => vec![(6, 11)])]
+    #[test_case("[a](b(c)d)" => vec![(3, 10)])]
+    fn test_link_target_ranges(s: &str) -> Vec<(usize, usize)> {
+        link_target_ranges(s)
+    }
+
+    #[test_case("see `Note` here", "Note" => Vec::<String>::new() ; "inline code excluded")]
+    #[test_case("see Note here", "Note" => vec!["Note".to_string()] ; "plain mention found")]
+    #[test_case("[x](Note)", "Note" => Vec::<String>::new() ; "link target excluded")]
+    #[test_case("```\nNote\n```", "Note" => Vec::<String>::new() ; "fenced code excluded")]
+    #[test_case("    Note", "Note" => Vec::<String>::new() ; "indented code excluded")]
+    fn test_accepted_matches_skips_code(content: &str, link: &str) -> Vec<String> {
+        let terms: BTreeMap<String, String> = vec![(link.to_string(), link.to_string())]
+            .into_iter()
+            .collect();
+        let index = Index::build(&terms, false);
+        accepted_matches(content, &index, true)
+            .into_iter()
+            .map(|(_, _, canonical)| canonical.to_string())
+            .collect()
+    }
+
+    #[test_case(0, "hello" => (1, 1))]
+    #[test_case(4, "hello" => (1, 5))]
+    #[test_case(6, "line1\nline2" => (2, 1))]
+    #[test_case(9, "line1\nline2" => (2, 4))]
+    #[test_case(6, "a\nb\nc\nd" => (4, 1))]
+    #[test_case(6, "héllo" => (1, 6) ; "multi-byte char counts as one column")]
+    #[test_case(9, "café\nworld" => (2, 4) ; "multi-byte char earlier on a previous line")]
+    fn test_line_column(byte_offset: usize, s: &str) -> (usize, usize) {
+        line_column(s, byte_offset)
+    }
+
+    #[test_case("human" => true)]
+    #[test_case("json" => true)]
+    #[test_case("ndjson" => true)]
+    #[test_case("yaml" => false)]
+    #[test_case("" => false)]
+    fn test_format_from_str(s: &str) -> bool {
+        s.parse::<Format>().is_ok()
+    }
+
+    fn test_settings(ignore: &[&str], min_mention_len: usize, stopwords: &[&str]) -> Settings {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in ignore {
+            for expanded in expand_ignore_pattern(pattern) {
+                builder.add(Glob::new(&expanded).unwrap());
+            }
+        }
+
+        Settings {
+            ignore_case: false,
+            ignore: builder.build().unwrap(),
+            min_mention_len,
+            stopwords: stopwords.iter().map(|s| s.to_lowercase()).collect(),
+        }
+    }
+
+    #[test_case(&["Note", "Index"], &["note", "index"] => vec!["Productivity".to_string()] ; "stopword filtered")]
+    #[test_case(&["Index"], &[] => vec!["Index".to_string(), "Productivity".to_string()] ; "no stopwords configured")]
+    fn test_filter_links(extra: &[&str], stopwords: &[&str]) -> Vec<String> {
+        let mut terms: BTreeMap<String, String> = extra
+            .iter()
+            .map(|s| (s.to_string(), s.to_string()))
+            .collect();
+        terms.insert("Productivity".to_string(), "Productivity".to_string());
+        let settings = test_settings(&[], 0, stopwords);
+        filter_links(terms, &settings)
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect()
+    }
+
+    #[test_case(0 => vec!["Hi".to_string(), "Productivity".to_string()])]
+    #[test_case(3 => vec!["Productivity".to_string()])]
+    fn test_filter_links_min_mention_len(min_mention_len: usize) -> Vec<String> {
+        let terms: BTreeMap<String, String> = vec!["Hi".to_string(), "Productivity".to_string()]
+            .into_iter()
+            .map(|s| (s.clone(), s))
+            .collect();
+        let settings = test_settings(&[], min_mention_len, &[]);
+        filter_links(terms, &settings)
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect()
+    }
+
+    #[test_case("notes", "templates/daily.md" => true)]
+    #[test_case("notes", "daily.md" => false)]
+    #[test_case("notes", "archive/old.md" => true)]
+    fn test_is_ignored(dir: &str, relative: &str) -> bool {
+        let settings = test_settings(&["templates/**", "archive/**"], 0, &[]);
+        is_ignored(
+            Path::new(dir),
+            &Path::new(dir).join(relative),
+            &settings.ignore,
+        )
+    }
+
+    #[test_case("notes", "templates/daily.md" => true ; "file inside bare dir pattern")]
+    #[test_case("notes", "templates" => true ; "dir itself matches bare dir pattern")]
+    #[test_case("notes", "daily.md" => false)]
+    fn test_is_ignored_bare_dir_pattern(dir: &str, relative: &str) -> bool {
+        let settings = test_settings(&["templates/"], 0, &[]);
+        is_ignored(
+            Path::new(dir),
+            &Path::new(dir).join(relative),
+            &settings.ignore,
+        )
+    }
+
+    #[test_case("templates/" => vec!["templates".to_string(), "templates/**".to_string()])]
+    #[test_case("archive/**" => vec!["archive/**".to_string()] ; "already a glob, left alone")]
+    #[test_case("*.tmp" => vec!["*.tmp".to_string()] ; "no trailing slash, left alone")]
+    fn test_expand_ignore_pattern(pattern: &str) -> Vec<String> {
+        expand_ignore_pattern(pattern)
+    }
 }